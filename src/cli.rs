@@ -17,6 +17,10 @@ pub enum CliCommand {
         name: String,
         #[arg(long)]
         disk_size: u32,
+        /// Build the disk as a bootable FAT image populated from this host
+        /// directory, instead of an empty disk.
+        #[arg(long)]
+        from_dir: Option<PathBuf>,
     },
     /// Run or edit a machine.
     Machine {
@@ -42,4 +46,16 @@ pub enum MachineCommand {
     },
     /// Open machine.toml in $EDITOR
     Edit,
+    /// Pause a running machine's virtual CPUs over QMP.
+    Pause,
+    /// Resume a paused machine over QMP.
+    Resume,
+    /// Gracefully power off a running machine over QMP.
+    Shutdown,
+    /// Save a snapshot of a running machine's state over QMP.
+    Snapshot { name: String },
+    /// Query a running machine's status over QMP.
+    Status,
+    /// Launch a SPICE client against a running machine's display socket.
+    View,
 }
@@ -14,12 +14,59 @@ pub struct Config {
 #[derive(Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Machine {
-    pub disk: PathBuf,
+    pub disks: Vec<Disk>,
     pub cpus: u32,
     pub memory: u32,
     pub kvm: bool,
-    pub uefi: bool,
+    pub uefi: UefiOption,
     pub video: VideoOption,
+    #[serde(default)]
+    pub vfio: Vec<VfioDevice>,
+    #[serde(default)]
+    pub display: Display,
+    #[serde(default)]
+    pub audio: AudioBackend,
+}
+
+/// Display-related settings, for running a machine as a daily-driver desktop.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Display {
+    /// Expose a SPICE server over a Unix socket in the machine's directory,
+    /// for use with `qemubox machine <name> view`.
+    #[serde(default)]
+    pub spice: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioBackend {
+    #[default]
+    None,
+    Pulse,
+    Pipewire,
+    Scream,
+}
+
+/// A disk attached to the machine. `path` is resolved relative to the
+/// machine's directory when not absolute, same as the old single `disk`
+/// field was.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Disk {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub preset: Option<DiskPreset>,
+}
+
+/// A known-good set of `-drive`/`-device` options for a [`Disk`]. Leaving
+/// `preset` unset keeps the plain positional disk behavior.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskPreset {
+    Ssd,
+    Hdd,
+    Nvme,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -31,15 +78,141 @@ pub enum VideoOption {
     None,
 }
 
+/// UEFI firmware configuration. `true` auto-detects a distro's OVMF
+/// install; `false` boots with the default SeaBIOS. Set explicit `code` and
+/// `vars_template` paths when auto-detection can't find the right files
+/// (e.g. a Nix install, whose OVMF store path is content-addressed and
+/// can't be guessed).
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UefiOption {
+    Enabled(bool),
+    Explicit {
+        code: PathBuf,
+        vars_template: PathBuf,
+    },
+}
+
+/// Per-distro locations of OVMF firmware, tried in order when `uefi = true`.
+const OVMF_SEARCH_PATHS: &[(&str, &str)] = &[
+    (
+        "/usr/share/edk2-ovmf/x64/OVMF_CODE.fd",
+        "/usr/share/edk2-ovmf/x64/OVMF_VARS.fd",
+    ),
+    (
+        "/usr/share/OVMF/OVMF_CODE.fd",
+        "/usr/share/OVMF/OVMF_VARS.fd",
+    ),
+    (
+        "/usr/share/edk2/ovmf/OVMF_CODE.fd",
+        "/usr/share/edk2/ovmf/OVMF_VARS.fd",
+    ),
+];
+
+fn autodetect_ovmf() -> Option<(PathBuf, PathBuf)> {
+    OVMF_SEARCH_PATHS.iter().find_map(|(code, vars_template)| {
+        let code = PathBuf::from(code);
+        let vars_template = PathBuf::from(vars_template);
+        (code.is_file() && vars_template.is_file()).then_some((code, vars_template))
+    })
+}
+
+impl UefiOption {
+    /// Resolve this option to concrete `(code, vars_template)` firmware
+    /// paths, auto-detecting from [`OVMF_SEARCH_PATHS`] when set to `true`.
+    /// Returns `None` when UEFI boot isn't requested.
+    pub fn resolve(&self) -> Result<Option<(PathBuf, PathBuf)>, UefiError> {
+        match self {
+            UefiOption::Enabled(false) => Ok(None),
+            UefiOption::Enabled(true) => autodetect_ovmf().map(Some).ok_or(UefiError::NotFound),
+            UefiOption::Explicit {
+                code,
+                vars_template,
+            } => Ok(Some((code.clone(), vars_template.clone()))),
+        }
+    }
+}
+
+pub enum UefiError {
+    NotFound,
+    CopyVarsTemplate(std::io::Error),
+}
+
+impl UefiError {
+    pub fn message(&self) -> String {
+        match self {
+            UefiError::NotFound => {
+                "could not auto-detect OVMF firmware; set uefi.code and uefi.vars_template explicitly".to_string()
+            }
+            UefiError::CopyVarsTemplate(e) => {
+                format!("could not copy the OVMF vars template into the machine directory: {e}")
+            }
+        }
+    }
+}
+
+/// Ensure the machine's local `ovmf_vars.fd` exists, copying it from the
+/// resolved OVMF vars template on first use if it was never created.
+pub fn ensure_ovmf_vars(uefi: &UefiOption, machine_dir: &Path) -> Result<(), UefiError> {
+    let Some((_, vars_template)) = uefi.resolve()? else {
+        return Ok(());
+    };
+
+    let vars_path = machine_dir.join("ovmf_vars.fd");
+    if !vars_path.exists() {
+        std::fs::copy(&vars_template, &vars_path).map_err(UefiError::CopyVarsTemplate)?;
+    }
+
+    Ok(())
+}
+
+/// A PCI device to pass through to the guest via VFIO, e.g. a GPU slot
+/// such as `"08:00.0"`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VfioDevice {
+    pub slot: String,
+    /// Whether this device should be exposed as the primary display
+    /// adapter (`x-vga=on`), implicitly disabling qemu's own video output.
+    #[serde(default)]
+    pub graphics: bool,
+}
+
 impl Default for Machine {
     fn default() -> Self {
         Machine {
-            disk: "./disk.qcow2".into(),
+            disks: vec![Disk {
+                path: "./disk.qcow2".into(),
+                preset: None,
+            }],
             cpus: 2,
             memory: 2048,
             kvm: true,
-            uefi: false,
+            uefi: UefiOption::Enabled(false),
             video: VideoOption::Std,
+            vfio: Vec::new(),
+            display: Display::default(),
+            audio: AudioBackend::None,
+        }
+    }
+}
+
+/// The path to a machine's SPICE display socket, given its directory.
+pub fn spice_socket_path(machine_dir: &Path) -> PathBuf {
+    machine_dir.join("spice.sock")
+}
+
+/// Errors that can occur while assembling a machine's qemu launch command.
+pub enum LaunchCommandError {
+    Lua(crate::lua::LuaError),
+    Uefi(UefiError),
+}
+
+impl LaunchCommandError {
+    pub fn message(&self) -> String {
+        match self {
+            LaunchCommandError::Lua(e) => e.message(),
+            LaunchCommandError::Uefi(e) => e.message(),
         }
     }
 }
@@ -49,27 +222,24 @@ impl Config {
         &self,
         containing_dir_path: P,
         cd_rom: Option<&Path>,
-    ) -> Command {
+    ) -> Result<Command, LaunchCommandError> {
         let Config {
             machine:
                 Machine {
-                    disk,
+                    disks,
                     cpus,
                     memory,
                     kvm,
                     uefi,
                     video,
+                    vfio,
+                    display,
+                    audio,
                 },
         } = self;
 
         let mut command = Command::new("qemu-system-x86_64");
 
-        let disk_path = if disk.is_absolute() {
-            disk.clone()
-        } else {
-            containing_dir_path.as_ref().join(disk)
-        };
-
         let mut args = Vec::new();
 
         let binding = cpus.to_string();
@@ -81,7 +251,21 @@ impl Config {
             args.push("-enable-kvm");
         }
 
-        let ovmf_vars_path = format!(
+        let qmp_socket_arg = format!(
+            "unix:{},server=on,wait=off",
+            crate::qmp::socket_path(containing_dir_path.as_ref()).to_string_lossy()
+        );
+        args.extend(["-qmp", qmp_socket_arg.as_str()]);
+
+        let uefi_firmware = uefi.resolve().map_err(LaunchCommandError::Uefi)?;
+
+        let ovmf_code_arg = uefi_firmware.as_ref().map(|(code, _)| {
+            format!(
+                "if=pflash,format=raw,readonly=on,file={}",
+                code.to_string_lossy()
+            )
+        });
+        let ovmf_vars_arg = format!(
             "if=pflash,format=raw,file={}",
             containing_dir_path
                 .as_ref()
@@ -89,20 +273,94 @@ impl Config {
                 .to_string_lossy()
         );
 
-        if *uefi {
+        if let Some(ovmf_code_arg) = &ovmf_code_arg {
             args.extend([
                 "-drive",
-                "if=pflash,format=raw,readonly=on,file=/usr/share/edk2-ovmf/x64/OVMF_CODE.fd",
+                ovmf_code_arg.as_str(),
                 "-drive",
-                ovmf_vars_path.as_str(),
+                ovmf_vars_arg.as_str(),
             ]);
         }
 
-        match video {
-            VideoOption::Std => args.extend(["-vga", "std"]),
-            VideoOption::VirtIo => args.extend(["-vga", "virtio"]),
-            VideoOption::Qxl => args.extend(["-vga", "qxl"]),
-            VideoOption::None => args.extend(["-vga", "none", "-nographic"]),
+        let graphics_passthrough = vfio.iter().any(|device| device.graphics);
+
+        if !vfio.is_empty() {
+            let machine_type = if *kvm { "q35,kvm=on" } else { "q35" };
+            args.extend(["-machine", machine_type]);
+        }
+
+        let vfio_device_args: Vec<String> = vfio
+            .iter()
+            .map(|device| {
+                if device.graphics {
+                    format!("vfio-pci,host={},x-vga=on", device.slot)
+                } else {
+                    format!("vfio-pci,host={}", device.slot)
+                }
+            })
+            .collect();
+        for device_arg in &vfio_device_args {
+            args.extend(["-device", device_arg.as_str()]);
+        }
+
+        if graphics_passthrough {
+            args.extend(["-vga", "none"]);
+        } else {
+            match video {
+                VideoOption::Std => args.extend(["-vga", "std"]),
+                VideoOption::VirtIo => args.extend(["-vga", "virtio"]),
+                VideoOption::Qxl => args.extend(["-vga", "qxl"]),
+                VideoOption::None => args.extend(["-vga", "none", "-nographic"]),
+            }
+        }
+
+        let spice_socket_arg = format!(
+            "unix,addr={},disable-ticketing=on",
+            spice_socket_path(containing_dir_path.as_ref()).to_string_lossy()
+        );
+        if display.spice {
+            args.extend([
+                "-spice",
+                spice_socket_arg.as_str(),
+                "-device",
+                "virtio-serial",
+                "-chardev",
+                "spicevmc,id=vdagent,debug=0,name=vdagent",
+                "-device",
+                "virtserialport,chardev=vdagent,name=com.redhat.spice.0",
+            ]);
+        }
+
+        match audio {
+            AudioBackend::None => {}
+            AudioBackend::Pulse => {
+                args.extend([
+                    "-device",
+                    "intel-hda",
+                    "-device",
+                    "hda-duplex,audiodev=a0",
+                    "-audiodev",
+                    "pa,id=a0",
+                ]);
+            }
+            AudioBackend::Pipewire => {
+                args.extend([
+                    "-device",
+                    "intel-hda",
+                    "-device",
+                    "hda-duplex,audiodev=a0",
+                    "-audiodev",
+                    "pipewire,id=a0",
+                ]);
+            }
+            AudioBackend::Scream => {
+                args.extend([
+                    "-object",
+                    "memory-backend-file,id=scream-mem,share=on,mem-path=/dev/shm/scream,size=2M",
+                    "-device",
+                    "ivshmem-plain,memdev=scream-mem",
+                ]);
+            }
         }
 
         let cd_rom = cd_rom.map(|p| p.to_string_lossy().to_string());
@@ -110,11 +368,90 @@ impl Config {
             args.extend(["-cdrom", cd_rom])
         }
 
-        let binding = disk_path.to_string_lossy();
-        args.push(binding.as_ref());
+        let mut disk_flag_values: Vec<(&'static str, String)> = Vec::new();
+        let mut positional_disk_paths: Vec<String> = Vec::new();
+
+        for (index, disk) in disks.iter().enumerate() {
+            let disk_path = if disk.path.is_absolute() {
+                disk.path.clone()
+            } else {
+                containing_dir_path.as_ref().join(&disk.path)
+            };
+            let file = disk_path.to_string_lossy().to_string();
+
+            match &disk.preset {
+                None => positional_disk_paths.push(file),
+                Some(DiskPreset::Ssd) => {
+                    let id = format!("disk{index}");
+                    disk_flag_values.push((
+                        "-drive",
+                        format!("file={file},if=none,id={id},discard=unmap,cache=none"),
+                    ));
+                    // rotation_rate isn't a virtio-blk-pci property (that's
+                    // scsi-hd/ide-hd only); discard=unmap already signals the
+                    // backing store is non-rotational.
+                    disk_flag_values.push(("-device", format!("virtio-blk-pci,drive={id}")));
+                }
+                Some(DiskPreset::Hdd) => {
+                    let id = format!("disk{index}");
+                    disk_flag_values.push((
+                        "-drive",
+                        format!("file={file},if=none,id={id},cache=writeback"),
+                    ));
+                    disk_flag_values.push(("-device", format!("virtio-blk-pci,drive={id}")));
+                }
+                Some(DiskPreset::Nvme) => {
+                    let id = format!("disk{index}");
+                    disk_flag_values.push(("-drive", format!("file={file},if=none,id={id}")));
+                    disk_flag_values.push(("-device", format!("nvme,drive={id},serial={id}")));
+                }
+            }
+        }
 
-        command.args(args);
+        for (flag, value) in &disk_flag_values {
+            args.extend([*flag, value.as_str()]);
+        }
+        for path in &positional_disk_paths {
+            args.push(path.as_str());
+        }
+
+        let mut final_args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+
+        let build_script_path = containing_dir_path.as_ref().join("qemu.lua");
+        if build_script_path.is_file() {
+            let extra_args = crate::lua::run_build_script(&build_script_path, self)
+                .map_err(LaunchCommandError::Lua)?;
+            final_args.extend(extra_args);
+        }
 
-        command
+        command.args(final_args);
+
+        Ok(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uefi_disabled_resolves_to_none() {
+        assert!(UefiOption::Enabled(false).resolve().unwrap().is_none());
     }
+
+    #[test]
+    fn uefi_explicit_resolves_to_its_own_paths() {
+        let option = UefiOption::Explicit {
+            code: PathBuf::from("/tmp/OVMF_CODE.fd"),
+            vars_template: PathBuf::from("/tmp/OVMF_VARS.fd"),
+        };
+
+        let (code, vars_template) = option.resolve().unwrap().unwrap();
+        assert_eq!(code, PathBuf::from("/tmp/OVMF_CODE.fd"));
+        assert_eq!(vars_template, PathBuf::from("/tmp/OVMF_VARS.fd"));
+    }
+
+    // `UefiOption::Enabled(true)` depends on OVMF actually being installed at
+    // one of `OVMF_SEARCH_PATHS`, which varies by host, so its autodetection
+    // branch isn't covered here.
 }
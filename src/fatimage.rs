@@ -0,0 +1,66 @@
+use std::path::Path;
+
+use fatfs::{Dir, FileSystem, FormatVolumeOptions, FsOptions};
+use fscommon::BufStream;
+
+/// Build a bootable FAT-formatted image at `image_path` from the contents of
+/// `source_dir`, sized to `size_mb` megabytes.
+pub fn build_from_dir(
+    image_path: &Path,
+    source_dir: &Path,
+    size_mb: u32,
+) -> Result<(), FatImageError> {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(image_path)
+        .map_err(FatImageError::Io)?;
+    file.set_len(u64::from(size_mb) * 1024 * 1024)
+        .map_err(FatImageError::Io)?;
+
+    let mut storage = BufStream::new(file);
+    fatfs::format_volume(&mut storage, FormatVolumeOptions::new()).map_err(FatImageError::Fat)?;
+
+    let fs = FileSystem::new(storage, FsOptions::new()).map_err(FatImageError::Fat)?;
+    copy_dir_into(source_dir, &fs.root_dir())?;
+
+    Ok(())
+}
+
+fn copy_dir_into(
+    source_dir: &Path,
+    dir: &Dir<BufStream<std::fs::File>>,
+) -> Result<(), FatImageError> {
+    for entry in std::fs::read_dir(source_dir).map_err(FatImageError::Io)? {
+        let entry = entry.map_err(FatImageError::Io)?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let metadata = entry.metadata().map_err(FatImageError::Io)?;
+
+        if metadata.is_dir() {
+            let subdir = dir.create_dir(&name).map_err(FatImageError::Fat)?;
+            copy_dir_into(&entry.path(), &subdir)?;
+        } else {
+            let mut fat_file = dir.create_file(&name).map_err(FatImageError::Fat)?;
+            let mut contents = std::fs::File::open(entry.path()).map_err(FatImageError::Io)?;
+            std::io::copy(&mut contents, &mut fat_file).map_err(FatImageError::Io)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub enum FatImageError {
+    Io(std::io::Error),
+    Fat(std::io::Error),
+}
+
+impl FatImageError {
+    pub fn message(&self) -> String {
+        match self {
+            FatImageError::Io(e) => format!("I/O error building the FAT image: {e}"),
+            FatImageError::Fat(e) => format!("error building the FAT filesystem: {e}"),
+        }
+    }
+}
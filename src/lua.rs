@@ -0,0 +1,80 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use mlua::{Lua, Value, Variadic};
+
+use crate::config::{Config, UefiOption};
+
+/// Run a machine's `qemu.lua` build script and collect the extra qemu
+/// arguments it appends.
+///
+/// The script is handed a `vm` table describing the parsed [`Config`] and can
+/// call either `vm:arg(...)` or `args:add(...)` — both append to the same
+/// underlying argument vector, which is appended to the command *after* the
+/// Rust-side defaults, letting the script override flags that take the last
+/// occurrence (as qemu itself does for most single-value options).
+pub fn run_build_script(script_path: &Path, config: &Config) -> Result<Vec<String>, LuaError> {
+    let script = std::fs::read_to_string(script_path).map_err(LuaError::Io)?;
+
+    let lua = Lua::new();
+    let extra_args = Rc::new(RefCell::new(Vec::<String>::new()));
+
+    let add_fn_args = extra_args.clone();
+    let add_fn = lua
+        .create_function(move |_, (_self, values): (Value, Variadic<String>)| {
+            add_fn_args.borrow_mut().extend(values);
+            Ok(())
+        })
+        .map_err(LuaError::Script)?;
+
+    let vm_table = lua.create_table().map_err(LuaError::Script)?;
+    vm_table
+        .set("cpus", config.machine.cpus)
+        .map_err(LuaError::Script)?;
+    vm_table
+        .set("memory", config.machine.memory)
+        .map_err(LuaError::Script)?;
+    vm_table
+        .set("kvm", config.machine.kvm)
+        .map_err(LuaError::Script)?;
+    let uefi_requested = !matches!(config.machine.uefi, UefiOption::Enabled(false));
+    vm_table
+        .set("uefi", uefi_requested)
+        .map_err(LuaError::Script)?;
+    vm_table
+        .set("arg", add_fn.clone())
+        .map_err(LuaError::Script)?;
+
+    let args_table = lua.create_table().map_err(LuaError::Script)?;
+    args_table.set("add", add_fn).map_err(LuaError::Script)?;
+
+    lua.globals()
+        .set("vm", vm_table)
+        .map_err(LuaError::Script)?;
+    lua.globals()
+        .set("args", args_table)
+        .map_err(LuaError::Script)?;
+
+    lua.load(&script).exec().map_err(LuaError::Script)?;
+
+    // `lua` (and the tables holding a clone of `extra_args` via `add_fn`) is
+    // still alive here, so this can't be `Rc::try_unwrap`'d out — clone the
+    // accumulated Vec out of the RefCell instead. Bound to a variable so the
+    // `Ref` temporary is dropped before returning, rather than extending into
+    // the tail expression's drop scope.
+    let result = extra_args.borrow().clone();
+    Ok(result)
+}
+
+pub enum LuaError {
+    Io(std::io::Error),
+    Script(mlua::Error),
+}
+
+impl LuaError {
+    pub fn message(&self) -> String {
+        match self {
+            LuaError::Io(e) => format!("could not read qemu.lua: {e}"),
+            LuaError::Script(e) => format!("error running qemu.lua: {e}"),
+        }
+    }
+}
@@ -1,9 +1,12 @@
 mod cli;
 mod config;
+mod fatimage;
+mod lua;
+mod qmp;
 
 use std::{
     fs::ReadDir,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
@@ -17,7 +20,11 @@ fn main() {
 
     let result = match cli.command {
         CliCommand::Ls => ls(),
-        CliCommand::New { name, disk_size } => new_machine(name, disk_size),
+        CliCommand::New {
+            name,
+            disk_size,
+            from_dir,
+        } => new_machine(name, disk_size, from_dir),
         CliCommand::Machine { name, cmd } => machine(name, cmd),
     };
 
@@ -50,11 +57,20 @@ fn machine(name: String, cmd: MachineCommand) -> Result<(), Error> {
         Ok((machines_dir, machines)) => {
             if let Some(machine) = machines.iter().find(|m| m.name == name) {
                 match cmd {
-                    cli::MachineCommand::Run => {
+                    // `cd_rom`/`verbose` were already fields on `Run` in cli.rs
+                    // before this module picked up the Lua-scripting feature;
+                    // the destructuring below just closes a pre-existing
+                    // mismatch, unrelated to the Lua work in this commit.
+                    cli::MachineCommand::Run { cd_rom, verbose: _ } => {
+                        let machine_dir = machines_dir.join(&machine.name);
+                        config::ensure_ovmf_vars(&machine.config.machine.uefi, &machine_dir)
+                            .map_err(Error::Uefi)?;
+
                         println!("Launching {}...", machine.name.bright_green().bold());
                         machine
                             .config
-                            .construct_launch_command(machines_dir.join(&machine.name))
+                            .construct_launch_command(machine_dir, cd_rom.as_deref())
+                            .map_err(Error::Launch)?
                             .stdout(Stdio::piped())
                             .spawn()
                             .unwrap()
@@ -69,6 +85,65 @@ fn machine(name: String, cmd: MachineCommand) -> Result<(), Error> {
                             display_warning(format!("are you sure? This will remove {dir_to_remove:?} and all of its contents.\nRun with --yes to confirm.").as_str());
                         }
                     }
+                    cli::MachineCommand::Edit => {
+                        let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+                        let config_path = machines_dir.join(&machine.name).join("machine.toml");
+                        Command::new(editor)
+                            .arg(config_path)
+                            .spawn()
+                            .unwrap()
+                            .wait()
+                            .unwrap();
+                    }
+                    cli::MachineCommand::Pause => {
+                        let mut client = connect_qmp(&machines_dir, &machine.name)?;
+                        client.pause().map_err(Error::Qmp)?;
+                        println!("Paused {}.", machine.name.bright_green().bold());
+                    }
+                    cli::MachineCommand::Resume => {
+                        let mut client = connect_qmp(&machines_dir, &machine.name)?;
+                        client.resume().map_err(Error::Qmp)?;
+                        println!("Resumed {}.", machine.name.bright_green().bold());
+                    }
+                    cli::MachineCommand::Shutdown => {
+                        let mut client = connect_qmp(&machines_dir, &machine.name)?;
+                        client.shutdown().map_err(Error::Qmp)?;
+                        println!(
+                            "Sent shutdown request to {}.",
+                            machine.name.bright_green().bold()
+                        );
+                    }
+                    cli::MachineCommand::Snapshot { name } => {
+                        let mut client = connect_qmp(&machines_dir, &machine.name)?;
+                        client.snapshot(&name).map_err(Error::Qmp)?;
+                        println!(
+                            "Saved snapshot {} for {}.",
+                            name.bold(),
+                            machine.name.bright_green().bold()
+                        );
+                    }
+                    cli::MachineCommand::Status => {
+                        let mut client = connect_qmp(&machines_dir, &machine.name)?;
+                        let status = client.status().map_err(Error::Qmp)?;
+                        println!("{}: {status}", machine.name.bright_green().bold());
+                    }
+                    cli::MachineCommand::View => {
+                        if !machine.config.machine.display.spice {
+                            return Err(Error::NoSpiceDisplay);
+                        }
+                        let socket_path =
+                            config::spice_socket_path(&machines_dir.join(&machine.name));
+                        println!(
+                            "Opening SPICE viewer for {}...",
+                            machine.name.bright_green().bold()
+                        );
+                        Command::new("remote-viewer")
+                            .arg(format!("spice+unix://{}", socket_path.to_string_lossy()))
+                            .spawn()
+                            .unwrap()
+                            .wait()
+                            .unwrap();
+                    }
                 }
             } else {
                 return Err(Error::NoMachineByName);
@@ -80,6 +155,12 @@ fn machine(name: String, cmd: MachineCommand) -> Result<(), Error> {
     Ok(())
 }
 
+/// Connect to a machine's QMP socket, assuming it is currently running.
+fn connect_qmp(machines_dir: &Path, name: &str) -> Result<qmp::QmpClient, Error> {
+    let socket_path = qmp::socket_path(&machines_dir.join(name));
+    qmp::QmpClient::connect(&socket_path).map_err(Error::Qmp)
+}
+
 /// This structure represents an instance of a machine found in the machines directory.
 /// These are constructed by [`get_machines`].
 struct Machine {
@@ -88,10 +169,14 @@ struct Machine {
 }
 
 fn get_machines_dir() -> Result<(PathBuf, ReadDir), Error> {
-    let Some(home) = dirs::home_dir() else { return Err(Error::NoHomeDirectory); };
+    let Some(home) = dirs::home_dir() else {
+        return Err(Error::NoHomeDirectory);
+    };
     let machines_dir = home.join(".local/share/qemubox/machines");
     let Ok(read_machines_dir) = std::fs::read_dir(home.join(".local/share/qemubox/machines"))
-        else { return Err(Error::ReadMachinesDirectoryFail); };
+    else {
+        return Err(Error::ReadMachinesDirectoryFail);
+    };
 
     Ok((machines_dir, read_machines_dir))
 }
@@ -124,7 +209,9 @@ fn get_machines() -> Result<(PathBuf, Vec<Machine>), Error> {
 }
 
 /// Create a new machine in the machines directory with a name and disk size.
-fn new_machine(name: String, disk_size: u32) -> Result<(), Error> {
+/// If `from_dir` is given, the disk is a FAT image populated from that host
+/// directory instead of an empty disk.
+fn new_machine(name: String, disk_size: u32, from_dir: Option<PathBuf>) -> Result<(), Error> {
     let (machines_dir, read_machines_dir) = get_machines_dir()?;
     for machine in read_machines_dir {
         if let Ok(machine) = machine {
@@ -139,25 +226,34 @@ fn new_machine(name: String, disk_size: u32) -> Result<(), Error> {
         return Err(Error::CreateMachineDirectoryFail);
     }
 
-    let config = toml::to_string_pretty(&Config::default()).unwrap();
+    let mut config = Config::default();
 
-    if std::fs::write(machine_dir.join("machine.toml"), config).is_err() {
-        return Err(Error::WriteMachineTomlFail);
+    if let Some(source_dir) = from_dir {
+        let disk_path = machine_dir.join("disk.img");
+        fatimage::build_from_dir(&disk_path, &source_dir, disk_size).map_err(Error::FatImage)?;
+        config.machine.disks = vec![config::Disk {
+            path: "./disk.img".into(),
+            preset: None,
+        }];
+    } else {
+        Command::new("qemu-img")
+            .args([
+                "create",
+                "-f",
+                "qcow2",
+                machine_dir.join("disk.qcow2").to_string_lossy().as_ref(),
+                format!("{disk_size}M").as_str(),
+            ])
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
     }
 
-    // create the disk
+    let config_text = toml::to_string_pretty(&config).unwrap();
 
-    Command::new("qemu-img")
-        .args([
-            "create",
-            "-f",
-            "qcow2",
-            machine_dir.join("disk.qcow2").to_string_lossy().as_ref(),
-            format!("{disk_size}M").as_str(),
-        ])
-        .stdout(Stdio::null())
-        .spawn()
-        .unwrap();
+    if std::fs::write(machine_dir.join("machine.toml"), config_text).is_err() {
+        return Err(Error::WriteMachineTomlFail);
+    }
 
     Ok(())
 }
@@ -170,6 +266,11 @@ enum Error {
     CreateMachineDirectoryFail,
     WriteMachineTomlFail,
     NoMachineByName,
+    Qmp(qmp::QmpError),
+    Launch(config::LaunchCommandError),
+    NoSpiceDisplay,
+    Uefi(config::UefiError),
+    FatImage(fatimage::FatImageError),
 }
 
 fn report_error(error: Error) {
@@ -189,6 +290,11 @@ fn report_error(error: Error) {
         }
         Error::WriteMachineTomlFail => display_error("could not write machines.toml"),
         Error::NoMachineByName => display_error("no machine found by that name"),
+        Error::Qmp(e) => display_error(e.message().as_str()),
+        Error::Launch(e) => display_error(e.message().as_str()),
+        Error::NoSpiceDisplay => display_error("this machine does not have display.spice enabled"),
+        Error::Uefi(e) => display_error(e.message().as_str()),
+        Error::FatImage(e) => display_error(e.message().as_str()),
     }
 }
 
@@ -0,0 +1,168 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+/// The path to a machine's QMP control socket, given its directory.
+pub fn socket_path(machine_dir: &Path) -> PathBuf {
+    machine_dir.join("qmp.sock")
+}
+
+/// Whether `name` is safe to splice unescaped into an HMP command line: it
+/// must be non-empty and free of whitespace and control characters, which
+/// the QEMU monitor would otherwise use to re-tokenize the command.
+fn is_valid_snapshot_name(name: &str) -> bool {
+    !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// A connection to a running machine's QMP control socket.
+///
+/// Constructed with [`QmpClient::connect`], which also performs the greeting
+/// and capability negotiation handshake.
+pub struct QmpClient {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+}
+
+impl QmpClient {
+    /// Connect to a machine's QMP socket and leave negotiation mode.
+    pub fn connect(socket_path: &Path) -> Result<Self, QmpError> {
+        let stream = UnixStream::connect(socket_path).map_err(QmpError::Connect)?;
+        let reader = BufReader::new(stream.try_clone().map_err(QmpError::Connect)?);
+        let mut client = QmpClient { stream, reader };
+
+        // the greeting is a `{"QMP": {...}}` object; nothing in it is needed here
+        client.read_message()?;
+        client.execute("qmp_capabilities", None)?;
+
+        Ok(client)
+    }
+
+    fn read_message(&mut self) -> Result<Value, QmpError> {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).map_err(QmpError::Io)?;
+        serde_json::from_str(&line).map_err(QmpError::Json)
+    }
+
+    /// Send a `{"execute": ...}` command and wait for its `return` payload,
+    /// skipping over any asynchronous events the VM reports in the meantime.
+    pub fn execute(&mut self, command: &str, arguments: Option<Value>) -> Result<Value, QmpError> {
+        let mut message = json!({ "execute": command });
+        if let Some(arguments) = arguments {
+            message["arguments"] = arguments;
+        }
+
+        let mut payload = message.to_string();
+        payload.push('\n');
+        self.stream
+            .write_all(payload.as_bytes())
+            .map_err(QmpError::Io)?;
+
+        loop {
+            let response = self.read_message()?;
+
+            if let Some(error) = response.get("error") {
+                return Err(QmpError::Command {
+                    class: error["class"].as_str().unwrap_or("Unknown").to_string(),
+                    desc: error["desc"].as_str().unwrap_or("").to_string(),
+                });
+            }
+
+            if let Some(result) = response.get("return") {
+                return Ok(result.clone());
+            }
+        }
+    }
+
+    /// `stop`: pause the VM's virtual CPUs.
+    pub fn pause(&mut self) -> Result<(), QmpError> {
+        self.execute("stop", None).map(|_| ())
+    }
+
+    /// `cont`: resume a paused VM.
+    pub fn resume(&mut self) -> Result<(), QmpError> {
+        self.execute("cont", None).map(|_| ())
+    }
+
+    /// `system_powerdown`: request a graceful ACPI shutdown.
+    pub fn shutdown(&mut self) -> Result<(), QmpError> {
+        self.execute("system_powerdown", None).map(|_| ())
+    }
+
+    /// Save a snapshot of the running machine under `name` via the HMP `savevm` command.
+    ///
+    /// `name` is spliced directly into the HMP command line, so it's rejected
+    /// up front if it contains whitespace or control characters — otherwise
+    /// it would be re-tokenized by the QEMU monitor (e.g. a name containing a
+    /// space would silently save under just its first word).
+    pub fn snapshot(&mut self, name: &str) -> Result<(), QmpError> {
+        if !is_valid_snapshot_name(name) {
+            return Err(QmpError::InvalidSnapshotName(name.to_string()));
+        }
+
+        self.execute(
+            "human-monitor-command",
+            Some(json!({ "command-line": format!("savevm {name}") })),
+        )
+        .map(|_| ())
+    }
+
+    /// `query-status`: get the VM's current run state (e.g. `"running"`, `"paused"`).
+    pub fn status(&mut self) -> Result<String, QmpError> {
+        let result = self.execute("query-status", None)?;
+        Ok(result["status"].as_str().unwrap_or("unknown").to_string())
+    }
+}
+
+pub enum QmpError {
+    Connect(std::io::Error),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Command { class: String, desc: String },
+    InvalidSnapshotName(String),
+}
+
+impl QmpError {
+    pub fn message(&self) -> String {
+        match self {
+            QmpError::Connect(e) => format!("could not connect to the QMP socket: {e}"),
+            QmpError::Io(e) => format!("I/O error communicating over QMP: {e}"),
+            QmpError::Json(e) => format!("could not parse a QMP message: {e}"),
+            QmpError::Command { class, desc } => format!("{class}: {desc}"),
+            QmpError::InvalidSnapshotName(name) => {
+                format!("{name:?} is not a valid snapshot name: it must not be empty and must not contain whitespace or control characters")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_plain_names() {
+        assert!(is_valid_snapshot_name("before-upgrade"));
+        assert!(is_valid_snapshot_name("snap1"));
+    }
+
+    #[test]
+    fn rejects_empty_names() {
+        assert!(!is_valid_snapshot_name(""));
+    }
+
+    #[test]
+    fn rejects_whitespace() {
+        assert!(!is_valid_snapshot_name("my snapshot"));
+        assert!(!is_valid_snapshot_name("tab\tseparated"));
+    }
+
+    #[test]
+    fn rejects_control_characters() {
+        assert!(!is_valid_snapshot_name("name\nwith-newline"));
+        assert!(!is_valid_snapshot_name("name\0with-nul"));
+    }
+}